@@ -0,0 +1,355 @@
+use stable::StableMutAddr;
+use std::io::Result;
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::slice;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+const GUARD_PAGES: usize = 1;
+
+const GROWTH_PAGES: usize = 4;
+
+const MAX_STACKS: usize = 128;
+
+pub struct GrowableStack {
+	base: *mut u8,
+	len: usize,
+	slot: usize,
+}
+
+impl GrowableStack {
+	pub fn new(len: usize) -> Result<Self> {
+		use libc::MAP_ANONYMOUS;
+		use libc::MAP_FAILED;
+		use libc::MAP_PRIVATE;
+		use libc::PROT_NONE;
+		use libc::PROT_READ;
+		use libc::PROT_WRITE;
+		use libc::mmap;
+		use libc::mprotect;
+		use libc::munmap;
+		use std::io::Error;
+		use std::io::ErrorKind;
+		use std::ptr::null_mut;
+
+		let page = page_size();
+		let len = round_up(len, page).max(page * (GUARD_PAGES + GROWTH_PAGES));
+
+		let base = unsafe {
+			mmap(null_mut(), len, PROT_NONE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0)
+		};
+		if base == MAP_FAILED {
+			Err(Error::last_os_error())?;
+		}
+		let base = base as *mut u8;
+
+		let committed = page * GROWTH_PAGES;
+		if unsafe {
+			mprotect(base.add(len - committed) as _, committed, PROT_READ | PROT_WRITE)
+		} != 0 {
+			let err = Error::last_os_error();
+			unsafe {
+				munmap(base as _, len);
+			}
+			Err(err)?;
+		}
+
+		let slot = match register(base as usize, len, page, len - committed) {
+			Some(slot) => slot,
+			None => {
+				unsafe {
+					munmap(base as _, len);
+				}
+				Err(Error::new(ErrorKind::Other, "GrowableStack::new(): guard-page registry is full"))?
+			},
+		};
+
+		Ok(Self {
+			base,
+			len,
+			slot,
+		})
+	}
+}
+
+impl Drop for GrowableStack {
+	fn drop(&mut self) {
+		use libc::munmap;
+
+		unregister(self.slot);
+		unsafe {
+			munmap(self.base as _, self.len);
+		}
+	}
+}
+
+impl Deref for GrowableStack {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		let guard = GUARD_PAGES * page_size();
+		unsafe {
+			slice::from_raw_parts(self.base.add(guard), self.len - guard)
+		}
+	}
+}
+
+impl DerefMut for GrowableStack {
+	fn deref_mut(&mut self) -> &mut [u8] {
+		let guard = GUARD_PAGES * page_size();
+		unsafe {
+			slice::from_raw_parts_mut(self.base.add(guard), self.len - guard)
+		}
+	}
+}
+
+unsafe impl StableMutAddr for GrowableStack {}
+
+struct Region {
+	base: AtomicUsize,
+	end: AtomicUsize,
+	floor: AtomicUsize,
+	fault: AtomicUsize,
+}
+
+impl Region {
+	const fn empty() -> Self {
+		Self {
+			base: AtomicUsize::new(0),
+			end: AtomicUsize::new(0),
+			floor: AtomicUsize::new(0),
+			fault: AtomicUsize::new(0),
+		}
+	}
+}
+
+const EMPTY_REGION: Region = Region::empty();
+static REGIONS: [Region; MAX_STACKS] = [EMPTY_REGION; MAX_STACKS];
+
+static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+fn register(base: usize, len: usize, page: usize, committed_from: usize) -> Option<usize> {
+	for (index, region) in REGIONS.iter().enumerate() {
+		if region.base.compare_exchange(0, base, Ordering::AcqRel, Ordering::Acquire).is_ok() {
+			region.end.store(base + len, Ordering::Release);
+			region.floor.store(base + GUARD_PAGES * page, Ordering::Release);
+			region.fault.store(committed_from, Ordering::Release);
+
+			return Some(index);
+		}
+	}
+
+	None
+}
+
+fn unregister(slot: usize) {
+	REGIONS[slot].base.store(0, Ordering::Release);
+}
+
+pub fn install_guard_handler() -> Result<()> {
+	use libc::SA_ONSTACK;
+	use libc::SA_SIGINFO;
+	use libc::SIGBUS;
+	use libc::SIGSEGV;
+	use libc::SIGSTKSZ;
+	use libc::sigaction;
+	use libc::sigaltstack;
+	use libc::stack_t;
+	use std::cell::UnsafeCell;
+	use std::io::Error;
+	use std::mem::zeroed;
+	use std::ptr::null_mut;
+	use std::thread_local;
+
+	thread_local! {
+		static ALTSTACK: UnsafeCell<[u8; SIGSTKSZ]> = UnsafeCell::new([0; SIGSTKSZ]);
+	}
+
+	PAGE_SIZE.store(page_size(), Ordering::Release);
+
+	let ss_sp = ALTSTACK.with(|altstack| unsafe {
+		(*altstack.get()).as_mut_ptr()
+	});
+	let altstack = stack_t {
+		ss_sp: ss_sp as _,
+		ss_flags: 0,
+		ss_size: SIGSTKSZ,
+	};
+	if unsafe {
+		sigaltstack(&altstack, null_mut())
+	} != 0 {
+		Err(Error::last_os_error())?;
+	}
+
+	let config = sigaction {
+		sa_flags: SA_SIGINFO | SA_ONSTACK,
+		sa_sigaction: handle_fault as _,
+		sa_restorer: None,
+		sa_mask: unsafe {
+			zeroed()
+		},
+	};
+	if unsafe {
+		sigaction(SIGSEGV, &config, null_mut()) != 0 || sigaction(SIGBUS, &config, null_mut()) != 0
+	} {
+		Err(Error::last_os_error())?;
+	}
+
+	Ok(())
+}
+
+extern "C" fn handle_fault(signal: libc::c_int, info: Option<&mut libc::siginfo_t>, _: Option<&mut libc::ucontext_t>) {
+	use libc::PROT_READ;
+	use libc::PROT_WRITE;
+	use libc::c_void;
+	use libc::mprotect;
+
+	let addr = match info {
+		Some(info) => unsafe {
+			info.si_addr()
+		} as usize,
+		None => 0,
+	};
+
+	for region in REGIONS.iter() {
+		let base = region.base.load(Ordering::Acquire);
+		if base == 0 {
+			continue;
+		}
+		let end = region.end.load(Ordering::Acquire);
+		if addr < base || addr >= end {
+			continue;
+		}
+
+		let floor = region.floor.load(Ordering::Acquire);
+		let fault = region.fault.load(Ordering::Acquire);
+		if addr < floor || addr >= fault {
+			break;
+		}
+
+		let page = PAGE_SIZE.load(Ordering::Acquire);
+		let grow = round_up(fault - addr, page) + page * GROWTH_PAGES;
+		let new_fault = fault.saturating_sub(grow).max(floor);
+		if unsafe {
+			mprotect(new_fault as *mut c_void, fault - new_fault, PROT_READ | PROT_WRITE)
+		} == 0 {
+			region.fault.store(new_fault, Ordering::Release);
+			return;
+		}
+
+		break;
+	}
+
+	reraise_default(signal);
+}
+
+fn reraise_default(signal: libc::c_int) {
+	use libc::SIG_DFL;
+	use libc::raise;
+	use libc::sigaction;
+	use std::mem::zeroed;
+	use std::ptr::null_mut;
+
+	let default = sigaction {
+		sa_flags: 0,
+		sa_sigaction: SIG_DFL,
+		sa_restorer: None,
+		sa_mask: unsafe {
+			zeroed()
+		},
+	};
+	unsafe {
+		sigaction(signal, &default, null_mut());
+		raise(signal);
+	}
+}
+
+fn page_size() -> usize {
+	use libc::_SC_PAGESIZE;
+	use libc::sysconf;
+
+	unsafe {
+		sysconf(_SC_PAGESIZE) as usize
+	}
+}
+
+fn round_up(len: usize, page: usize) -> usize {
+	(len + page - 1) / page * page
+}
+
+#[cfg(test)]
+mod tests {
+	use super::GROWTH_PAGES;
+	use super::GUARD_PAGES;
+	use super::GrowableStack;
+	use super::install_guard_handler;
+	use super::page_size;
+
+	#[test]
+	fn growablestack_newhaslen() {
+		let stack = GrowableStack::new(1).unwrap();
+		assert!(stack.len() >= 1);
+		assert_eq!(stack.len() % super::page_size(), 0);
+	}
+
+	#[test]
+	fn guardhandler_extends_growth_zone() {
+		use std::ptr::read_volatile;
+		use std::ptr::write_volatile;
+
+		install_guard_handler().unwrap();
+
+		let page = page_size();
+		let stack = GrowableStack::new(page * (GUARD_PAGES + GROWTH_PAGES + 2)).unwrap();
+		let target = unsafe {
+			stack.base.add(page)
+		};
+
+		unsafe {
+			write_volatile(target, 0x42u8);
+			assert_eq!(read_volatile(target), 0x42u8);
+		}
+	}
+
+	#[test]
+	fn guardhandler_overflow_terminates() {
+		use libc::SIGBUS;
+		use libc::SIGSEGV;
+		use libc::WIFSIGNALED;
+		use libc::WTERMSIG;
+		use libc::_exit;
+		use libc::c_int;
+		use libc::fork;
+		use libc::waitpid;
+		use std::ptr::write_volatile;
+
+		let pid = unsafe {
+			fork()
+		};
+		assert!(pid >= 0);
+		if pid == 0 {
+			install_guard_handler().unwrap();
+
+			let page = page_size();
+			let stack = GrowableStack::new(page * (GUARD_PAGES + GROWTH_PAGES + 2)).unwrap();
+			let target = unsafe {
+				stack.base.add(page / 2)
+			};
+			unsafe {
+				write_volatile(target, 0x42u8);
+			}
+
+			unsafe {
+				_exit(1);
+			}
+		}
+
+		let mut status: c_int = 0;
+		unsafe {
+			waitpid(pid, &mut status, 0);
+		}
+		assert!(WIFSIGNALED(status));
+		assert!(WTERMSIG(status) == SIGSEGV || WTERMSIG(status) == SIGBUS);
+	}
+}
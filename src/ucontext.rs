@@ -16,11 +16,46 @@ pub struct Context<S: DerefMut<Target = [u8]>> {
 
 pub struct HandlerContext (ucontext_t);
 
+pub struct FrameWalk {
+	fp: *const usize,
+	lower: *const u8,
+	upper: *const u8,
+}
+
+impl Iterator for FrameWalk {
+	type Item = *const ();
+
+	fn next(&mut self) -> Option<Self::Item> {
+		use std::mem::align_of;
+		use std::mem::size_of;
+
+		let fp = self.fp as *const u8;
+		if fp.is_null()
+			|| fp as usize % align_of::<usize>() != 0
+			|| fp < self.lower
+			|| fp.wrapping_add(2 * size_of::<usize>()) > self.upper
+		{
+			None?;
+		}
+
+		let ret = unsafe {
+			*self.fp.add(1)
+		} as *const ();
+		self.fp = unsafe {
+			*self.fp
+		} as *const usize;
+
+		Some(ret)
+	}
+}
+
 struct Persistent<S: DerefMut<Target = [u8]>> {
 	stack: S,
 	successor: Id,
 }
 
+unsafe impl<S: Send + StableMutAddr<Target = [u8]>> Send for Context<S> {}
+
 pub fn getcontext<T, A: FnOnce(Context<Void>) -> T, B: FnMut() -> T>(scope: A, mut checkpoint: B) -> Result<T> {
 	use libc::getcontext;
 	use std::mem::forget;
@@ -106,8 +141,18 @@ pub fn makecontext<S: DerefMut<Target = [u8]>, F: FnOnce(Context<S>)>(stack: S,
 	Ok(())
 }
 
-pub fn restorecontext<S: StableMutAddr<Target = [u8]>, F: FnOnce(Context<S>)>(persistent: Context<S>, scope: F) -> Result<()> {
-	unimplemented!()
+pub fn restorecontext<S: StableMutAddr<Target = [u8]>, F: FnOnce(Context<S>)>(mut persistent: Context<S>, scope: F) -> Result<()> {
+	getcontext(
+		|successor| -> Result<()> {
+			persistent.migrate(&successor)?;
+			scope(persistent);
+
+			Ok(())
+		},
+		|| Ok(()),
+	)??;
+
+	Ok(())
 }
 
 #[must_use]
@@ -161,7 +206,269 @@ impl<S: DerefMut<Target = [u8]>> Context<S> {
 	}
 
 	pub fn swap(&mut self, other: &mut HandlerContext) {
-		unimplemented!();
+		use invar::MoveInvariant;
+		use std::mem::swap;
+
+		let mut this = self.context.borrow_mut();
+
+		this.after_move();
+		other.0.after_move();
+
+		swap(&mut *this, &mut other.0);
+
+		this.after_move();
+		other.0.after_move();
+	}
+
+	pub fn walk_frames(&self) -> FrameWalk {
+		use libc::REG_RBP;
+
+		let context = self.context.borrow();
+		let fp = context.uc_mcontext.gregs[REG_RBP as usize] as *const usize;
+		let (lower, upper) = self.persistent.as_ref().map(|persistent| {
+			let stack: &[u8] = &persistent.stack;
+			(stack.as_ptr(), unsafe {
+				stack.as_ptr().add(stack.len())
+			})
+		}).unwrap_or_else(current_stack_bounds);
+
+		FrameWalk {
+			fp,
+			lower,
+			upper,
+		}
+	}
+}
+
+impl<S: StableMutAddr<Target = [u8]>> Context<S> {
+	fn migrate<T: DerefMut<Target = [u8]>>(&mut self, successor: &Context<T>) -> Result<()> {
+		use invar::MoveInvariant;
+
+		self.id = self.id.migrate()?;
+		self.context.borrow_mut().after_move();
+		if let Some(persistent) = self.persistent.as_mut() {
+			persistent.successor = successor.id;
+			self.context.borrow_mut().uc_link = successor.context.as_ptr();
+		}
+
+		Ok(())
+	}
+}
+
+pub type Compress = unsafe extern "C" fn(input: *const u8, input_len: usize, output: *mut u8, output_len: *mut usize) -> bool;
+
+pub type Uncompress = unsafe extern "C" fn(input: *const u8, input_len: usize, output: *mut u8, output_len: *mut usize) -> bool;
+
+const FREEZE_FORMAT: u32 = 2;
+const RELOC_SS_SP: u8 = 1 << 0;
+const RELOC_RSP: u8 = 1 << 2;
+const RELOC_RBP: u8 = 1 << 3;
+
+fn to_stack_offset(ptr: usize, base: usize, len: usize) -> Option<usize> {
+	if ptr >= base && ptr <= base + len {
+		Some(ptr - base)
+	} else {
+		None
+	}
+}
+
+impl<S: StableMutAddr<Target = [u8]>> Context<S> {
+	pub fn freeze(&self) -> Vec<u8> {
+		self.freeze_with(None)
+	}
+
+	pub fn freeze_with(&self, compress: Option<Compress>) -> Vec<u8> {
+		use invar::MoveInvariant;
+		use libc::REG_RBP;
+		use libc::REG_RSP;
+		use std::mem::size_of;
+		use std::ptr::null_mut;
+		use std::slice::from_raw_parts;
+
+		let persistent = self.persistent.as_ref()
+			.expect("freeze(): only a makecontext()-created Context owns a relocatable stack");
+		let base = persistent.stack.as_ptr() as usize;
+		let len = persistent.stack.len();
+
+		let mut context = self.context.borrow_mut();
+		context.after_move();
+
+		let mut flags = 0u8;
+		macro_rules! relocate {
+			($place:expr, $bit:expr) => {
+				if let Some(offset) = to_stack_offset($place as usize, base, len) {
+					$place = offset as _;
+					flags |= $bit;
+				}
+			};
+		}
+		relocate!(context.uc_stack.ss_sp, RELOC_SS_SP);
+		relocate!(context.uc_mcontext.gregs[REG_RSP as usize], RELOC_RSP);
+		relocate!(context.uc_mcontext.gregs[REG_RBP as usize], RELOC_RBP);
+
+		// uc_link points outside this stack, so there's nothing to relocate
+		// it against; null it here and restore the real value below.
+		let uc_link = context.uc_link;
+		context.uc_link = null_mut();
+
+		let mut buf = Vec::new();
+		buf.extend_from_slice(&FREEZE_FORMAT.to_ne_bytes());
+		buf.push(flags);
+		buf.extend_from_slice(unsafe {
+			from_raw_parts(&*context as *const ucontext_t as *const u8, size_of::<ucontext_t>())
+		});
+
+		macro_rules! restore {
+			($place:expr, $bit:expr, $orig:expr) => {
+				if flags & $bit != 0 {
+					$place = $orig as _;
+				}
+			};
+		}
+		restore!(context.uc_stack.ss_sp, RELOC_SS_SP, base + context.uc_stack.ss_sp as usize);
+		restore!(context.uc_mcontext.gregs[REG_RSP as usize], RELOC_RSP, base + context.uc_mcontext.gregs[REG_RSP as usize] as usize);
+		restore!(context.uc_mcontext.gregs[REG_RBP as usize], RELOC_RBP, base + context.uc_mcontext.gregs[REG_RBP as usize] as usize);
+		context.uc_link = uc_link;
+
+		let stack: &[u8] = &persistent.stack;
+		buf.extend_from_slice(&(len as u64).to_ne_bytes());
+		match compress.and_then(|compress| compress_stack(compress, stack)) {
+			Some(compressed) => {
+				buf.push(1);
+				buf.extend_from_slice(&(compressed.len() as u64).to_ne_bytes());
+				buf.extend_from_slice(&compressed);
+			},
+			None => {
+				buf.push(0);
+				buf.extend_from_slice(&(len as u64).to_ne_bytes());
+				buf.extend_from_slice(stack);
+			},
+		}
+
+		buf
+	}
+
+	pub fn thaw(buf: &[u8], stack: S) -> Result<Self> {
+		Self::thaw_with(buf, stack, None)
+	}
+
+	pub fn thaw_with(buf: &[u8], mut stack: S, uncompress: Option<Uncompress>) -> Result<Self> {
+		use invar::MoveInvariant;
+		use libc::REG_RBP;
+		use libc::REG_RSP;
+		use std::io::Cursor;
+		use std::io::ErrorKind;
+		use std::io::Read;
+		use std::mem::size_of;
+		use std::slice::from_raw_parts_mut;
+
+		let invalid = |message: &str| Error::new(ErrorKind::InvalidData, message.to_owned());
+
+		let mut cursor = Cursor::new(buf);
+		let mut scratch = [0u8; 8];
+
+		cursor.read_exact(&mut scratch[.. 4]).map_err(|_| invalid("thaw(): truncated buffer"))?;
+		if u32::from_ne_bytes([scratch[0], scratch[1], scratch[2], scratch[3]]) != FREEZE_FORMAT {
+			Err(invalid("thaw(): unsupported freeze() format version"))?;
+		}
+
+		let mut flags = [0u8; 1];
+		cursor.read_exact(&mut flags).map_err(|_| invalid("thaw(): truncated buffer"))?;
+		let flags = flags[0];
+
+		let mut raw = ucontext_t::uninit();
+		cursor.read_exact(unsafe {
+			from_raw_parts_mut(&mut raw as *mut ucontext_t as *mut u8, size_of::<ucontext_t>())
+		}).map_err(|_| invalid("thaw(): truncated buffer"))?;
+
+		cursor.read_exact(&mut scratch).map_err(|_| invalid("thaw(): truncated buffer"))?;
+		let stack_len = u64::from_ne_bytes(scratch) as usize;
+
+		let mut compressed = [0u8; 1];
+		cursor.read_exact(&mut compressed).map_err(|_| invalid("thaw(): truncated buffer"))?;
+		let compressed = compressed[0] != 0;
+
+		cursor.read_exact(&mut scratch).map_err(|_| invalid("thaw(): truncated buffer"))?;
+		let payload_len = u64::from_ne_bytes(scratch) as usize;
+
+		let mut payload = vec![0u8; payload_len];
+		cursor.read_exact(&mut payload).map_err(|_| invalid("thaw(): truncated buffer"))?;
+
+		if stack.len() < stack_len {
+			Err(invalid("thaw(): target stack is smaller than the frozen one"))?;
+		}
+
+		if compressed {
+			let uncompress = uncompress.ok_or_else(|| invalid("thaw(): frozen stack is compressed but no uncompress callback was given"))?;
+			let mut written = stack_len;
+			if ! unsafe {
+				uncompress(payload.as_ptr(), payload.len(), stack.as_mut_ptr(), &mut written)
+			} || written != stack_len {
+				Err(invalid("thaw(): uncompress callback failed"))?;
+			}
+		} else {
+			stack[.. stack_len].copy_from_slice(&payload);
+		}
+
+		let base = stack.as_ptr() as usize;
+		macro_rules! rebase {
+			($place:expr, $bit:expr) => {
+				if flags & $bit != 0 {
+					$place = (base + $place as usize) as _;
+				}
+			};
+		}
+		rebase!(raw.uc_stack.ss_sp, RELOC_SS_SP);
+		rebase!(raw.uc_mcontext.gregs[REG_RSP as usize], RELOC_RSP);
+		rebase!(raw.uc_mcontext.gregs[REG_RBP as usize], RELOC_RBP);
+
+		// raw.uc_link is null, so keep persistent.successor in sync by
+		// seeding it already-invalidated rather than Id::new()'s default.
+		let no_successor = Id::new();
+		no_successor.invalidate();
+
+		let mut this = Self::new(stack, no_successor);
+		*this.context.borrow_mut() = raw;
+		this.context.borrow_mut().after_move();
+
+		Ok(this)
+	}
+}
+
+fn compress_stack(compress: Compress, stack: &[u8]) -> Option<Vec<u8>> {
+	let mut out = vec![0u8; stack.len()];
+	let mut out_len = out.len();
+	if unsafe {
+		compress(stack.as_ptr(), stack.len(), out.as_mut_ptr(), &mut out_len)
+	} {
+		out.truncate(out_len);
+		Some(out)
+	} else {
+		None
+	}
+}
+
+fn current_stack_bounds() -> (*const u8, *const u8) {
+	use libc::pthread_attr_destroy;
+	use libc::pthread_attr_getstack;
+	use libc::pthread_attr_t;
+	use libc::pthread_getattr_np;
+	use libc::pthread_self;
+	use std::ptr::null;
+	use std::ptr::null_mut;
+
+	let mut attr = pthread_attr_t::uninit();
+	unsafe {
+		if pthread_getattr_np(pthread_self(), &mut attr) != 0 {
+			return (null(), null());
+		}
+
+		let mut base = null_mut();
+		let mut size = 0;
+		pthread_attr_getstack(&attr, &mut base, &mut size);
+		pthread_attr_destroy(&mut attr);
+
+		(base as *const u8, (base as *const u8).add(size))
 	}
 }
 
@@ -218,4 +525,79 @@ mod tests {
 			context.add(1)
 		}
 	}
+
+	#[test]
+	fn context_restorecontext() {
+		use stack::GrowableStack;
+		use super::current_stack_bounds;
+		use super::makecontext;
+		use super::restorecontext;
+
+		extern "C" fn call() {}
+
+		let stack = GrowableStack::new(4096).unwrap();
+		let mut created = None;
+		makecontext(stack, |context| created = Some(context), call).unwrap();
+		let persistent = created.unwrap();
+		let old_uc_link = persistent.context.borrow().uc_link;
+
+		restorecontext(persistent, |migrated| {
+			assert!(migrated.persistent.as_ref().unwrap().successor.is_valid());
+
+			let uc_link = migrated.context.borrow().uc_link;
+			assert_ne!(uc_link, old_uc_link);
+
+			let (lower, upper) = current_stack_bounds();
+			assert!(! lower.is_null());
+			assert!((uc_link as *const u8) >= lower && (uc_link as *const u8) < upper);
+		}).unwrap();
+	}
+
+	#[test]
+	fn context_walkframes() {
+		use super::current_stack_bounds;
+
+		let context = getcontext(|context| context, || unreachable!()).unwrap();
+
+		let frames: Vec<_> = context.walk_frames().take(256).collect();
+		assert!(frames.len() >= 2, "expected at least a couple of frames on the call stack");
+		assert!(frames.len() < 256, "walk_frames() did not terminate within a sane number of frames");
+
+		let (lower, upper) = current_stack_bounds();
+		for frame in frames {
+			assert!(! frame.is_null());
+			assert!((frame as *const u8) < lower || (frame as *const u8) >= upper);
+		}
+	}
+
+	#[test]
+	fn context_stackoffset() {
+		use super::to_stack_offset;
+
+		assert_eq!(to_stack_offset(104, 100, 16), Some(4));
+		assert_eq!(to_stack_offset(99, 100, 16), None);
+		assert_eq!(to_stack_offset(116, 100, 16), Some(16));
+		assert_eq!(to_stack_offset(117, 100, 16), None);
+	}
+
+	#[test]
+	fn context_freezethaw_stacktop() {
+		use id::Id;
+		use libc::REG_RSP;
+		use stack::GrowableStack;
+		use super::Context;
+
+		let stack = GrowableStack::new(4096).unwrap();
+		let top = stack.as_ptr() as i64 + stack.len() as i64;
+
+		let context = Context::new(stack, Id::new());
+		context.context.borrow_mut().uc_mcontext.gregs[REG_RSP as usize] = top;
+
+		let buf = context.freeze();
+		let thawed = Context::thaw(&buf, GrowableStack::new(4096).unwrap()).unwrap();
+
+		let persistent = thawed.persistent.as_ref().unwrap();
+		let rebased_top = persistent.stack.as_ptr() as i64 + persistent.stack.len() as i64;
+		assert_eq!(thawed.context.borrow().uc_mcontext.gregs[REG_RSP as usize], rebased_top);
+	}
 }